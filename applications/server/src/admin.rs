@@ -0,0 +1,138 @@
+use std::{path::Path, sync::Arc};
+
+use iroh::NodeId;
+use iroh_blobs::store::fs::FsStore;
+use proto_control::{JobManager, JobStatusKind, StaticAllowList};
+use tokio::{
+	io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+	net::{UnixListener, UnixStream},
+};
+use tracing::{info, warn};
+
+use crate::config::Config;
+
+/// Local AF_UNIX admin protocol: one command line in, one reply out (several
+/// lines for `JOBS` and `ALLOW LIST`), then close.
+///
+/// Runs alongside the iroh `Router`, sharing the same `Arc<FsStore>` and
+/// `JobManager`, so operators can inspect and steer a running daemon without
+/// needing an authorized iroh peer — handy for scripting and systemd on the Pi.
+///
+/// `config_path` is the file `ALLOW ADD`/`ALLOW REMOVE` save back to, so a
+/// live edit here survives the allow-list reload `spawn_reload_on_sighup`
+/// runs on SIGHUP instead of being reconciled away.
+pub async fn serve(
+	sock_path: &str,
+	config_path: Arc<str>,
+	node_id: NodeId,
+	allow_list: Arc<StaticAllowList>,
+	jobs: Arc<JobManager>,
+	store: Arc<FsStore>,
+) -> std::io::Result<()> {
+	// Best-effort cleanup of a stale socket left behind by a previous run.
+	let _ = std::fs::remove_file(sock_path);
+	let listener = UnixListener::bind(sock_path)?;
+	info!(path = sock_path, "admin socket listening");
+
+	loop {
+		let (stream, _) = listener.accept().await?;
+		let config_path = config_path.clone();
+		let allow_list = allow_list.clone();
+		let jobs = jobs.clone();
+		let store = store.clone();
+		tokio::spawn(async move {
+			if let Err(e) = handle_conn(stream, &config_path, node_id, allow_list, jobs, store).await {
+				warn!(%e, "admin connection error");
+			}
+		});
+	}
+}
+
+async fn handle_conn(
+	stream: UnixStream,
+	config_path: &str,
+	node_id: NodeId,
+	allow_list: Arc<StaticAllowList>,
+	jobs: Arc<JobManager>,
+	store: Arc<FsStore>,
+) -> std::io::Result<()> {
+	let (read_half, mut write_half) = stream.into_split();
+	let mut lines = BufReader::new(read_half).lines();
+	let Some(line) = lines.next_line().await? else {
+		return Ok(());
+	};
+
+	let response = dispatch(&line, config_path, node_id, &allow_list, &jobs, &store).await;
+	write_half.write_all(response.as_bytes()).await?;
+	write_half.write_all(b"\n").await?;
+	Ok(())
+}
+
+async fn dispatch(
+	line: &str,
+	config_path: &str,
+	node_id: NodeId,
+	allow_list: &StaticAllowList,
+	jobs: &JobManager,
+	store: &FsStore,
+) -> String {
+	let mut parts = line.split_whitespace();
+	match parts.next() {
+		Some("NODE_ID") => node_id.to_string(),
+		Some("JOBS") => jobs
+			.list()
+			.into_iter()
+			.map(|(job_id, job)| {
+				let status = match job.status {
+					JobStatusKind::Running => "running",
+					JobStatusKind::Done => "done",
+					JobStatusKind::Cancelled => "cancelled",
+				};
+				format!(
+					"{job_id}\t{}\t{}/{} downloaded\t{} failed\t{status}",
+					job.device_tag, job.downloaded, job.total, job.failed
+				)
+			})
+			.collect::<Vec<_>>()
+			.join("\n"),
+		Some("ALLOW") => match (parts.next(), parts.next()) {
+			(Some("ADD"), Some(id)) => match id.parse() {
+				Ok(id) => {
+					allow_list.add(id);
+					persist_allow_list(config_path, allow_list)
+				}
+				Err(_) => format!("error: bad node id: {id}"),
+			},
+			(Some("REMOVE"), Some(id)) => match id.parse() {
+				Ok(id) => {
+					allow_list.remove(&id);
+					persist_allow_list(config_path, allow_list)
+				}
+				Err(_) => format!("error: bad node id: {id}"),
+			},
+			(Some("LIST"), None) => {
+				allow_list.list().iter().map(NodeId::to_string).collect::<Vec<_>>().join("\n")
+			}
+			_ => "error: usage: ALLOW ADD|REMOVE <node_id> | ALLOW LIST".to_string(),
+		},
+		Some("GC") => match store.gc_run().await {
+			Ok(()) => "ok".to_string(),
+			Err(e) => format!("error: {e}"),
+		},
+		Some(other) => format!("error: unknown command {other}"),
+		None => "error: empty command".to_string(),
+	}
+}
+
+/// Save the allow-list's current contents back to `config_path`, so the
+/// edit survives the next SIGHUP reload instead of being reverted by it.
+/// A save failure is reported but doesn't undo the already-applied
+/// in-memory change — the grant is just transient until the operator fixes
+/// whatever kept the file from being written.
+fn persist_allow_list(config_path: &str, allow_list: &StaticAllowList) -> String {
+	let ids = allow_list.list().into_iter().collect();
+	match Config::save_allow_node_ids(Path::new(config_path), &ids) {
+		Ok(()) => "ok".to_string(),
+		Err(e) => format!("ok (warning: failed to persist to {config_path}: {e})"),
+	}
+}
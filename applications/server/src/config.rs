@@ -0,0 +1,154 @@
+use std::{collections::HashSet, env, path::Path, str::FromStr, time::Duration};
+
+use proto_control::RetryConfig;
+use serde::{Deserialize, Serialize};
+use snafu::Snafu;
+
+/// Default config file path if `--config`/`HOMENECT_CONFIG` isn't set.
+pub const DEFAULT_CONFIG_PATH: &str = "/etc/homenect.toml";
+
+/// Settings loaded from a TOML file, with environment variables overriding
+/// individual fields on top. Centralizes what used to be scattered `HOMENECT_*`
+/// env vars and hardcoded constants.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct Config {
+	pub store_path: Option<String>,
+	pub allow_node_ids: Option<Vec<String>>,
+	pub control_max_bytes: Option<usize>,
+	pub admin_sock: Option<String>,
+	pub download_timeout_secs: Option<u64>,
+	pub max_attempts: Option<u32>,
+	pub backoff_base_ms: Option<u64>,
+	pub backoff_cap_secs: Option<u64>,
+}
+
+#[derive(Debug, Snafu)]
+pub enum ConfigError {
+	#[snafu(display("failed to read config {path}: {source}"))]
+	Read {
+		path: String,
+		source: std::io::Error,
+	},
+
+	#[snafu(display("failed to parse config {path}: {source}"))]
+	Parse {
+		path: String,
+		source: toml::de::Error,
+	},
+
+	#[snafu(display("failed to serialize config {path}: {source}"))]
+	Serialize {
+		path: String,
+		source: toml::ser::Error,
+	},
+
+	#[snafu(display("failed to write config {path}: {source}"))]
+	Write {
+		path: String,
+		source: std::io::Error,
+	},
+}
+
+impl Config {
+	/// Load `path` if it exists (an absent file is not an error — every
+	/// field then falls back to its default), then apply env var overrides.
+	pub fn load(path: &Path) -> Result<Self, ConfigError> {
+		let mut config = Self::read_file(path)?;
+		config.apply_env_overrides();
+		Ok(config)
+	}
+
+	/// Raw file contents, with no env var overrides applied — the form
+	/// that round-trips through [`Config::save_allow_node_ids`]. Also what
+	/// a SIGHUP reload should reconcile the live allow-list against, so an
+	/// env-set `HOMENECT_ALLOW_NODE_IDS` (which only seeds the list at
+	/// startup) doesn't silently revert an admin-socket edit the file has
+	/// since picked up.
+	pub(crate) fn read_file(path: &Path) -> Result<Self, ConfigError> {
+		if path.exists() {
+			let raw = std::fs::read_to_string(path).map_err(|e| ConfigError::Read {
+				path: path.display().to_string(),
+				source: e,
+			})?;
+			toml::from_str(&raw).map_err(|e| ConfigError::Parse {
+				path: path.display().to_string(),
+				source: e,
+			})
+		} else {
+			Ok(Config::default())
+		}
+	}
+
+	/// Persist `ids` as the file's `allow_node_ids`, leaving every other
+	/// field untouched. Used by the admin socket's `ALLOW ADD`/`ALLOW REMOVE`
+	/// so a live grant survives the next SIGHUP reload instead of being
+	/// reconciled away.
+	pub fn save_allow_node_ids(path: &Path, ids: &HashSet<iroh::NodeId>) -> Result<(), ConfigError> {
+		let mut config = Self::read_file(path)?;
+		let mut ids: Vec<String> = ids.iter().map(iroh::NodeId::to_string).collect();
+		ids.sort();
+		config.allow_node_ids = Some(ids);
+
+		let raw = toml::to_string_pretty(&config).map_err(|e| ConfigError::Serialize {
+			path: path.display().to_string(),
+			source: e,
+		})?;
+		std::fs::write(path, raw).map_err(|e| ConfigError::Write {
+			path: path.display().to_string(),
+			source: e,
+		})
+	}
+
+	fn apply_env_overrides(&mut self) {
+		if let Ok(v) = env::var("HOMENECT_STORE_PATH") {
+			self.store_path = Some(v);
+		}
+		if let Ok(v) = env::var("HOMENECT_ALLOW_NODE_IDS") {
+			self.allow_node_ids = Some(v.split(',').map(|s| s.trim().to_string()).collect());
+		}
+		if let Ok(v) = env::var("HOMENECT_ADMIN_SOCK") {
+			self.admin_sock = Some(v);
+		}
+	}
+
+	pub fn store_path(&self) -> String {
+		self.store_path.clone().unwrap_or_else(|| "/srv/homenect/store".to_string())
+	}
+
+	pub fn allow_node_ids(&self) -> HashSet<iroh::NodeId> {
+		self.allow_node_ids
+			.as_deref()
+			.unwrap_or(&[])
+			.iter()
+			.filter_map(|s| iroh::NodeId::from_str(s.trim()).ok())
+			.collect()
+	}
+
+	pub fn control_max_bytes(&self) -> usize {
+		self.control_max_bytes.unwrap_or(proto_control::DEFAULT_CONTROL_MAX_BYTES)
+	}
+
+	pub fn retry_config(&self) -> RetryConfig {
+		let default = RetryConfig::default();
+		RetryConfig {
+			download_timeout: self
+				.download_timeout_secs
+				.map(Duration::from_secs)
+				.unwrap_or(default.download_timeout),
+			max_attempts: self.max_attempts.unwrap_or(default.max_attempts),
+			backoff_base: self.backoff_base_ms.map(Duration::from_millis).unwrap_or(default.backoff_base),
+			backoff_cap: self.backoff_cap_secs.map(Duration::from_secs).unwrap_or(default.backoff_cap),
+		}
+	}
+}
+
+/// Resolve the config file path: `--config <path>` wins, then
+/// `HOMENECT_CONFIG`, then [`DEFAULT_CONFIG_PATH`].
+pub fn config_path(args: &[String]) -> String {
+	args.iter()
+		.position(|a| a == "--config")
+		.and_then(|i| args.get(i + 1))
+		.cloned()
+		.or_else(|| env::var("HOMENECT_CONFIG").ok())
+		.unwrap_or_else(|| DEFAULT_CONFIG_PATH.to_string())
+}
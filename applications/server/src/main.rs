@@ -1,12 +1,17 @@
-use std::{env, str::FromStr, sync::Arc};
+use std::sync::Arc;
 
 use iroh::{protocol::RouterBuilder, Endpoint};
 use iroh_blobs::{store::fs::FsStore, BlobsProtocol, ALPN as BLOBS_ALPN};
-use proto_control::{ControlHandler, CONTROL_ALPN};
+use proto_control::{Authorizer, ControlHandler, JobManager, StaticAllowList, CONTROL_ALPN};
 use snafu::Snafu;
-use tracing::info;
+use tracing::{info, warn};
 use tracing_subscriber::EnvFilter;
 
+mod admin;
+mod config;
+
+use config::Config;
+
 // Errors
 #[derive(Debug, Snafu)]
 enum MainError {
@@ -30,6 +35,11 @@ enum MainError {
 	RouterShutdown {
 		details: String,
 	},
+
+	#[snafu(display("failed to load config: {source}"))]
+	Config {
+		source: config::ConfigError,
+	},
 }
 
 async fn init_endpoint() -> Result<Endpoint, MainError> {
@@ -45,12 +55,6 @@ async fn load_fs_store(path: &str) -> Result<FsStore, MainError> {
 	})
 }
 
-// put this anywhere above main()
-fn parse_allow_list(csv: Option<String>) -> std::collections::HashSet<iroh::NodeId> {
-	csv.map(|v| v.split(',').filter_map(|s| iroh::NodeId::from_str(s.trim()).ok()).collect())
-		.unwrap_or_default()
-}
-
 // Main
 #[tokio::main]
 async fn main() -> Result<(), MainError> {
@@ -60,14 +64,20 @@ async fn main() -> Result<(), MainError> {
 		.compact()
 		.init();
 
-	let store_path =
-		env::var("HOMENECT_STORE_PATH").unwrap_or_else(|_| "/srv/homenect/store".to_string());
-	let allow = parse_allow_list(env::var("HOMENECT_ALLOW_NODE_IDS").ok());
+	let args: Vec<String> = std::env::args().collect();
+	let path = config::config_path(&args);
+	let cfg = Config::load(std::path::Path::new(&path)).map_err(|source| MainError::Config {
+		source,
+	})?;
+
+	let allow_list = Arc::new(StaticAllowList::new(cfg.allow_node_ids()));
+	let authorizer: Arc<dyn Authorizer> = allow_list.clone();
+	let jobs = JobManager::new();
 
 	let endpoint = init_endpoint().await?;
 	let endpoint_arc = Arc::new(endpoint.clone());
 
-	let fs_store = Arc::new(load_fs_store(&store_path).await?);
+	let fs_store = Arc::new(load_fs_store(&cfg.store_path()).await?);
 
 	// Register blobs protocol for data-path
 	let blobs = BlobsProtocol::new(&fs_store, endpoint.clone(), None);
@@ -76,11 +86,48 @@ async fn main() -> Result<(), MainError> {
 		let builder: RouterBuilder = iroh::protocol::Router::builder(endpoint.clone());
 		builder
 			.accept(BLOBS_ALPN, blobs.clone())
-			// ControlHandler expects Arc<Endpoint> and Arc<FsStore>
-			.accept(CONTROL_ALPN.as_bytes(), ControlHandler::new(allow, endpoint_arc, fs_store))
+			// ControlHandler expects Arc<dyn Authorizer>, Arc<Endpoint>, Arc<FsStore>, Arc<JobManager>,
+			// a RetryConfig and a control message size limit.
+			.accept(
+				CONTROL_ALPN.as_bytes(),
+				ControlHandler::with_config(
+					authorizer,
+					endpoint_arc,
+					fs_store.clone(),
+					jobs.clone(),
+					cfg.retry_config(),
+					cfg.control_max_bytes(),
+				),
+			)
 			.spawn()
 	};
 
+	let config_path: Arc<str> = Arc::from(path.as_str());
+
+	if let Some(admin_sock) = cfg.admin_sock.clone() {
+		let node_id = endpoint.node_id();
+		let admin_jobs = jobs.clone();
+		let admin_store = fs_store.clone();
+		let admin_allow_list = allow_list.clone();
+		let admin_config_path = config_path.clone();
+		tokio::spawn(async move {
+			if let Err(e) = admin::serve(
+				&admin_sock,
+				admin_config_path,
+				node_id,
+				admin_allow_list,
+				admin_jobs,
+				admin_store,
+			)
+			.await
+			{
+				warn!(%e, "admin socket stopped");
+			}
+		});
+	}
+
+	spawn_reload_on_sighup(path, allow_list.clone());
+
 	info!(node_id = %endpoint.node_id(), "server started");
 	tokio::signal::ctrl_c().await.map_err(|source| MainError::Signal {
 		source,
@@ -90,3 +137,39 @@ async fn main() -> Result<(), MainError> {
 	})?;
 	Ok(())
 }
+
+/// On SIGHUP, reload the config file and apply any allow-list changes live,
+/// without restarting the process.
+///
+/// Reconciles against the *file's* persisted allow-list (`Config::read_file`),
+/// not `Config::load`'s env-overridden view: the admin socket's `ALLOW
+/// ADD`/`ALLOW REMOVE` persist straight to the file, so reconciling against
+/// `HOMENECT_ALLOW_NODE_IDS` instead would silently revert them on every
+/// reload. The env var still seeds the allow-list once at startup.
+fn spawn_reload_on_sighup(path: String, allow_list: Arc<StaticAllowList>) {
+	let Ok(mut hangup) = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+	else {
+		warn!("could not install SIGHUP handler; allow-list reload disabled");
+		return;
+	};
+	tokio::spawn(async move {
+		while hangup.recv().await.is_some() {
+			let cfg = match Config::read_file(std::path::Path::new(&path)) {
+				Ok(cfg) => cfg,
+				Err(e) => {
+					warn!(%e, "SIGHUP: failed to reload config, keeping current allow-list");
+					continue;
+				}
+			};
+			let wanted = cfg.allow_node_ids();
+			let current: std::collections::HashSet<_> = allow_list.list().into_iter().collect();
+			for id in wanted.difference(&current) {
+				allow_list.add(*id);
+			}
+			for id in current.difference(&wanted) {
+				allow_list.remove(id);
+			}
+			info!(allowed = wanted.len(), "SIGHUP: reloaded allow-list from config");
+		}
+	});
+}
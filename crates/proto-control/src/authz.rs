@@ -0,0 +1,238 @@
+use std::{
+	collections::{BTreeSet, HashSet},
+	future::Future,
+	pin::Pin,
+	sync::Mutex,
+	time::{SystemTime, UNIX_EPOCH},
+};
+
+use ed25519_dalek::Signature;
+use iroh::NodeId;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::{BeginBackup, ControlError};
+
+/// Capability a peer was granted for a specific request.
+///
+/// `None` on any field means "unrestricted" for that axis.
+#[derive(Debug, Clone, Default)]
+pub struct Authorization {
+	pub allowed_tags: Option<HashSet<String>>,
+	/// Cumulative bytes this grant may pull across `tickets` in one `Begin`.
+	/// Enforced by the `Begin` download task, which stops starting new
+	/// tickets once the running total meets or exceeds this; tickets already
+	/// in flight when the quota is hit are not aborted mid-transfer.
+	pub byte_quota: Option<u64>,
+	pub max_tickets: Option<usize>,
+}
+
+impl Authorization {
+	/// No restrictions beyond having been authorized at all.
+	pub fn unrestricted() -> Self {
+		Self::default()
+	}
+
+	/// Check the grant against the request it was issued for.
+	pub fn permits(&self, req: &BeginBackup) -> bool {
+		if let Some(tags) = &self.allowed_tags {
+			if !tags.contains(&req.device_tag) {
+				return false;
+			}
+		}
+		if let Some(max) = self.max_tickets {
+			if req.tickets.len() > max {
+				return false;
+			}
+		}
+		true
+	}
+}
+
+/// Policy deciding what a connecting peer may do.
+///
+/// Implementations replace the old fixed `allow_node_ids` check: `ControlHandler`
+/// calls `authorize` with the peer's `NodeId` and the parsed request, and gets back
+/// either a capability or a `ControlError::Unauthorized`.
+pub trait Authorizer: std::fmt::Debug + Send + Sync {
+	/// Authorize `peer`'s request, returning the capability it was granted.
+	fn authorize<'a>(
+		&'a self,
+		peer: NodeId,
+		req: &'a BeginBackup,
+	) -> Pin<Box<dyn Future<Output = Result<Authorization, ControlError>> + Send + 'a>>;
+
+	/// Identity-only check for operations that carry no `BeginBackup`
+	/// (`Status`, `Cancel`, `List`, `Verify`). `token` is carried out-of-band
+	/// on `RequestEnvelope` for exactly these ops, since none of them has a
+	/// `BeginBackup` of its own to hold one. The default probes `authorize`
+	/// with an empty request plus that token, applying the same policy
+	/// `Begin` would for a peer asking for nothing in particular — which
+	/// means implementations keyed purely on the token (like
+	/// `CapabilityTokenAuthorizer`) work correctly without an override;
+	/// override only if an implementation needs a cheaper or different check.
+	fn authorize_peer<'a>(
+		&'a self,
+		peer: NodeId,
+		token: Option<&'a str>,
+	) -> Pin<Box<dyn Future<Output = Result<(), ControlError>> + Send + 'a>> {
+		Box::pin(async move {
+			let probe = BeginBackup {
+				device_tag: String::new(),
+				tickets: Vec::new(),
+				token: token.map(str::to_string),
+			};
+			self.authorize(peer, &probe).await.map(|_| ())
+		})
+	}
+}
+
+/// Reproduces the old behavior: a set of allowed `NodeId`s, unrestricted once in.
+///
+/// The set lives behind a `Mutex` rather than being baked in at construction
+/// so it can be steered live, e.g. from the admin socket.
+#[derive(Debug)]
+pub struct StaticAllowList {
+	allow_node_ids: Mutex<HashSet<NodeId>>,
+}
+
+impl StaticAllowList {
+	pub fn new(allow_node_ids: HashSet<NodeId>) -> Self {
+		Self {
+			allow_node_ids: Mutex::new(allow_node_ids),
+		}
+	}
+
+	/// Grant `id` access, effective for the next connection it opens.
+	pub fn add(&self, id: NodeId) {
+		self.allow_node_ids.lock().unwrap().insert(id);
+	}
+
+	/// Revoke `id`'s access; does not affect connections already in progress.
+	pub fn remove(&self, id: &NodeId) {
+		self.allow_node_ids.lock().unwrap().remove(id);
+	}
+
+	/// Current allow-list, e.g. for the admin socket's `ALLOW LIST`.
+	pub fn list(&self) -> Vec<NodeId> {
+		self.allow_node_ids.lock().unwrap().iter().copied().collect()
+	}
+}
+
+impl Authorizer for StaticAllowList {
+	fn authorize<'a>(
+		&'a self,
+		peer: NodeId,
+		_req: &'a BeginBackup,
+	) -> Pin<Box<dyn Future<Output = Result<Authorization, ControlError>> + Send + 'a>> {
+		Box::pin(async move {
+			if self.allow_node_ids.lock().unwrap().contains(&peer) {
+				Ok(Authorization::unrestricted())
+			} else {
+				warn!(%peer, "peer not in static allow list");
+				Err(ControlError::Unauthorized)
+			}
+		})
+	}
+}
+
+/// Claims carried by a capability token, signed by a trusted issuer.
+#[derive(Debug, Serialize, Deserialize)]
+struct TokenClaims {
+	peer: NodeId,
+	/// Ordered set, so re-serializing the claims for signature checking
+	/// yields the same bytes the issuer signed.
+	allowed_tags: Option<BTreeSet<String>>,
+	byte_quota: Option<u64>,
+	max_tickets: Option<usize>,
+	/// Unix timestamp (seconds): the token is rejected at or after this
+	/// instant. Required, not optional — this is what makes a grant
+	/// "time-bounded" rather than a forever-valid, forever-replayable bearer
+	/// token.
+	exp: u64,
+	/// Unix timestamp (seconds) before which the token isn't valid yet.
+	#[serde(default)]
+	nbf: Option<u64>,
+}
+
+/// A capability token as carried in `BeginBackup::token`: the claims plus a
+/// hex-encoded ed25519 signature over their canonical JSON, made by `issuer`.
+#[derive(Debug, Deserialize)]
+struct CapabilityToken {
+	claims: TokenClaims,
+	signature: String,
+}
+
+/// Verifies capability tokens signed by a single trusted issuer key.
+///
+/// Grants described by a valid token let operators hand out time-bounded, per-tag
+/// capabilities without the server knowing about them ahead of time.
+#[derive(Debug)]
+pub struct CapabilityTokenAuthorizer {
+	issuer: NodeId,
+}
+
+impl CapabilityTokenAuthorizer {
+	pub fn new(issuer: NodeId) -> Self {
+		Self {
+			issuer,
+		}
+	}
+}
+
+impl Authorizer for CapabilityTokenAuthorizer {
+	fn authorize<'a>(
+		&'a self,
+		peer: NodeId,
+		req: &'a BeginBackup,
+	) -> Pin<Box<dyn Future<Output = Result<Authorization, ControlError>> + Send + 'a>> {
+		Box::pin(async move {
+			let Some(raw) = &req.token else {
+				warn!(%peer, "no capability token presented");
+				return Err(ControlError::Unauthorized);
+			};
+
+			let token: CapabilityToken = serde_json::from_str(raw).map_err(|e| {
+				warn!(%peer, %e, "malformed capability token");
+				ControlError::Unauthorized
+			})?;
+
+			let canonical = serde_json::to_vec(&token.claims).map_err(|_| ControlError::Unauthorized)?;
+			let sig_bytes = hex::decode(&token.signature).map_err(|_| ControlError::Unauthorized)?;
+			let sig_bytes: [u8; 64] = sig_bytes.try_into().map_err(|_| ControlError::Unauthorized)?;
+			let signature = Signature::from_bytes(&sig_bytes);
+
+			self.issuer.verify(&canonical, &signature).map_err(|e| {
+				warn!(%peer, %e, "capability token signature invalid");
+				ControlError::Unauthorized
+			})?;
+
+			if token.claims.peer != peer {
+				warn!(%peer, "capability token issued to a different peer");
+				return Err(ControlError::Unauthorized);
+			}
+
+			let now = SystemTime::now()
+				.duration_since(UNIX_EPOCH)
+				.map(|d| d.as_secs())
+				.unwrap_or(0);
+			if now >= token.claims.exp {
+				warn!(%peer, exp = token.claims.exp, now, "capability token expired");
+				return Err(ControlError::Unauthorized);
+			}
+			if let Some(nbf) = token.claims.nbf {
+				if now < nbf {
+					warn!(%peer, nbf, now, "capability token not yet valid");
+					return Err(ControlError::Unauthorized);
+				}
+			}
+
+			debug!(%peer, "capability token accepted");
+			Ok(Authorization {
+				allowed_tags: token.claims.allowed_tags.map(|tags| tags.into_iter().collect()),
+				byte_quota: token.claims.byte_quota,
+				max_tickets: token.claims.max_tickets,
+			})
+		})
+	}
+}
@@ -0,0 +1,155 @@
+use std::{
+	collections::{HashMap, VecDeque},
+	sync::{Arc, Mutex},
+};
+
+use tokio::task::AbortHandle;
+
+/// How many completed jobs `JobManager` keeps around for `Status`/`List` to
+/// still find, after which the oldest are evicted. Caps the map's memory for
+/// a long-running daemon instead of retaining every job for the process's
+/// whole lifetime; running jobs are never evicted, only terminal (`Done` or
+/// `Cancelled`) ones.
+const MAX_RETAINED_DONE: usize = 200;
+
+/// Coarse lifecycle of a job, independent of its per-ticket counters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobStatusKind {
+	Running,
+	Done,
+	/// Stopped early by a `Cancel` request rather than running to completion.
+	Cancelled,
+}
+
+/// Result of a `Cancel` request against [`JobManager::cancel`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CancelOutcome {
+	Cancelled,
+	NotFound,
+	/// The job had already reached a terminal state; there was nothing left
+	/// in flight to abort.
+	AlreadyDone,
+}
+
+/// Current state of one job, in flight or completed.
+#[derive(Debug, Clone)]
+pub struct JobState {
+	pub device_tag: String,
+	pub total: usize,
+	pub downloaded: usize,
+	pub failed: usize,
+	pub status: JobStatusKind,
+}
+
+/// The two pieces of state `JobManager` keeps, behind one `Mutex` so they
+/// can't drift out of sync with each other (`done_order`'s length is only a
+/// correct eviction count as long as it's updated in lockstep with `jobs`).
+#[derive(Debug, Default)]
+struct JobsInner {
+	jobs: HashMap<u64, JobState>,
+	/// Ids of jobs that reached a terminal state (`Done` or `Cancelled`),
+	/// oldest first, for evicting once [`MAX_RETAINED_DONE`] is exceeded.
+	done_order: VecDeque<u64>,
+	/// Handles for the `Begin` download task of each still-`Running` job, so
+	/// `cancel` can actually stop the task instead of just relabeling it.
+	/// Entries are removed once a job reaches a terminal state.
+	abort_handles: HashMap<u64, AbortHandle>,
+}
+
+impl JobsInner {
+	/// Shared tail end of both a job running to completion and a job being
+	/// cancelled: mark it terminal, drop its abort handle, and evict the
+	/// oldest terminal jobs past [`MAX_RETAINED_DONE`].
+	fn mark_terminal(&mut self, job_id: u64, status: JobStatusKind) {
+		if let Some(job) = self.jobs.get_mut(&job_id) {
+			job.status = status;
+		}
+		self.abort_handles.remove(&job_id);
+
+		self.done_order.push_back(job_id);
+		while self.done_order.len() > MAX_RETAINED_DONE {
+			if let Some(evicted) = self.done_order.pop_front() {
+				self.jobs.remove(&evicted);
+			}
+		}
+	}
+}
+
+/// Tracks jobs the server has started, keyed by job id.
+///
+/// `ControlHandler` owns one `JobManager` alongside its other shared state.
+/// The `Begin` download loop updates it as tickets complete, on its own
+/// Tokio task, so a job outlives the connection that started it; `Status`
+/// and `List` requests read from the same map. Running jobs are kept
+/// forever; once a job finishes, only the most recent [`MAX_RETAINED_DONE`]
+/// are retained so the map doesn't grow without bound over the daemon's
+/// lifetime.
+#[derive(Debug, Default)]
+pub struct JobManager {
+	inner: Mutex<JobsInner>,
+}
+
+impl JobManager {
+	pub fn new() -> Arc<Self> {
+		Arc::new(Self::default())
+	}
+
+	pub fn start(&self, job_id: u64, device_tag: String, total: usize) {
+		self.inner.lock().unwrap().jobs.insert(
+			job_id,
+			JobState {
+				device_tag,
+				total,
+				downloaded: 0,
+				failed: 0,
+				status: JobStatusKind::Running,
+			},
+		);
+	}
+
+	pub fn record(&self, job_id: u64, downloaded: usize, failed: usize) {
+		if let Some(job) = self.inner.lock().unwrap().jobs.get_mut(&job_id) {
+			job.downloaded = downloaded;
+			job.failed = failed;
+		}
+	}
+
+	pub fn finish(&self, job_id: u64) {
+		self.inner.lock().unwrap().mark_terminal(job_id, JobStatusKind::Done);
+	}
+
+	/// Record the `AbortHandle` for `job_id`'s `Begin` download task, so
+	/// [`cancel`](Self::cancel) can stop it. Called once, right after the
+	/// task is spawned; a no-op if the job has already finished by then.
+	pub fn set_abort_handle(&self, job_id: u64, handle: AbortHandle) {
+		let mut inner = self.inner.lock().unwrap();
+		if inner.jobs.get(&job_id).is_some_and(|job| job.status == JobStatusKind::Running) {
+			inner.abort_handles.insert(job_id, handle);
+		}
+	}
+
+	/// Abort `job_id`'s download task and mark it `Cancelled`.
+	pub fn cancel(&self, job_id: u64) -> CancelOutcome {
+		let mut inner = self.inner.lock().unwrap();
+		let Some(job) = inner.jobs.get(&job_id) else {
+			return CancelOutcome::NotFound;
+		};
+		if job.status != JobStatusKind::Running {
+			return CancelOutcome::AlreadyDone;
+		}
+
+		if let Some(handle) = inner.abort_handles.get(&job_id) {
+			handle.abort();
+		}
+		inner.mark_terminal(job_id, JobStatusKind::Cancelled);
+		CancelOutcome::Cancelled
+	}
+
+	pub fn get(&self, job_id: u64) -> Option<JobState> {
+		self.inner.lock().unwrap().jobs.get(&job_id).cloned()
+	}
+
+	pub fn list(&self) -> Vec<(u64, JobState)> {
+		self.inner.lock().unwrap().jobs.iter().map(|(id, s)| (*id, s.clone())).collect()
+	}
+}
@@ -1,9 +1,6 @@
-use std::{
-	collections::HashSet,
-	sync::{
-		atomic::{AtomicU64, Ordering},
-		Arc,
-	},
+use std::sync::{
+	atomic::{AtomicU64, Ordering},
+	Arc,
 };
 
 use iroh::{
@@ -15,19 +12,42 @@ use iroh::{
 use iroh_blobs::{store::fs::FsStore, ticket::BlobTicket};
 use serde::{Deserialize, Serialize};
 use snafu::Snafu;
-use tracing::{debug, error, info, instrument};
+use tracing::{debug, error, info, instrument, warn};
+
+mod authz;
+mod job;
+mod protocol;
+mod retry;
+
+pub use authz::{Authorization, Authorizer, CapabilityTokenAuthorizer, StaticAllowList};
+pub use job::{CancelOutcome, JobManager, JobState, JobStatusKind};
+pub use protocol::{
+	ControlRequest,
+	ControlResponse,
+	JobStatus,
+	JobSummary,
+	RequestEnvelope,
+	ResponseEnvelope,
+	VerifyResult,
+	CONTROL_PROTOCOL_VERSION,
+};
+pub use retry::{download_with_retry, RetryConfig, TicketOutcome};
 
 /// ALPN for our tiny control plane.
 pub const CONTROL_ALPN: &str = "/homenect/control/1";
 
-/// A hard upper bound for control messages (JSON).
-const CONTROL_MAX_BYTES: usize = 4 * 1024 * 1024; // 4 MiB
+/// Default hard upper bound for control messages (JSON), used unless a
+/// `ControlHandler` is built with an explicit limit via [`ControlHandler::with_config`].
+pub const DEFAULT_CONTROL_MAX_BYTES: usize = 4 * 1024 * 1024; // 4 MiB
 
 /// Client → Server: ask the Pi to pull these tickets.
 #[derive(Debug, Deserialize)]
 pub struct BeginBackup {
 	pub device_tag: String,
 	pub tickets: Vec<String>,
+	/// Opaque capability token; interpretation is up to the configured `Authorizer`.
+	#[serde(default)]
+	pub token: Option<String>,
 }
 
 /// Server → Client: completion
@@ -36,7 +56,13 @@ pub struct CompletionAck {
 	pub job_id: u64,
 	pub ok: bool,
 	pub downloaded: usize,
+	/// Of `downloaded`, how many only succeeded after at least one retry.
+	pub retried: usize,
 	pub failed: usize,
+	/// Tickets never attempted because `byte_quota` was already met. A
+	/// within-policy quota stop isn't a failure: these don't count toward
+	/// `failed` or `ok`.
+	pub quota_skipped: usize,
 	pub error: Option<String>,
 }
 
@@ -46,6 +72,11 @@ pub enum ControlError {
 	#[snafu(display("unauthorized peer"))]
 	Unauthorized,
 
+	#[snafu(display("unsupported protocol version {got} (we speak {CONTROL_PROTOCOL_VERSION})"))]
+	UnsupportedVersion {
+		got: u16,
+	},
+
 	// read_to_end returns iroh_quinn::ReadToEndError; keep it boxed for stability.
 	#[snafu(display("read failed: {source}"))]
 	Read {
@@ -75,24 +106,63 @@ pub enum ControlError {
 	},
 }
 
-/// Control handler: auth by NodeId, read JSON, pull blobs via FsStore downloader.
+/// Control handler: read JSON, authorize against the parsed request, pull blobs via
+/// FsStore downloader.
 #[derive(Debug)]
 pub struct ControlHandler {
-	allow_node_ids: HashSet<NodeId>,
+	authorizer: Arc<dyn Authorizer>,
 	job_seq: Arc<AtomicU64>,
+	jobs: Arc<JobManager>,
+	retry: RetryConfig,
+	max_request_bytes: usize,
 	endpoint: Arc<Endpoint>,
 	store: Arc<FsStore>,
 }
 
 impl ControlHandler {
 	pub fn new(
-		allow_node_ids: HashSet<NodeId>,
+		authorizer: Arc<dyn Authorizer>,
 		endpoint: Arc<Endpoint>,
 		store: Arc<FsStore>,
+	) -> Arc<Self> {
+		Self::with_job_manager(authorizer, endpoint, store, JobManager::new())
+	}
+
+	/// As [`ControlHandler::new`], but sharing a `JobManager` with another
+	/// listener (e.g. the admin socket) instead of owning a private one.
+	pub fn with_job_manager(
+		authorizer: Arc<dyn Authorizer>,
+		endpoint: Arc<Endpoint>,
+		store: Arc<FsStore>,
+		jobs: Arc<JobManager>,
+	) -> Arc<Self> {
+		Self::with_config(
+			authorizer,
+			endpoint,
+			store,
+			jobs,
+			RetryConfig::default(),
+			DEFAULT_CONTROL_MAX_BYTES,
+		)
+	}
+
+	/// As [`ControlHandler::with_job_manager`], with explicit download
+	/// timeout/retry bounds and control message size limit instead of the
+	/// defaults.
+	pub fn with_config(
+		authorizer: Arc<dyn Authorizer>,
+		endpoint: Arc<Endpoint>,
+		store: Arc<FsStore>,
+		jobs: Arc<JobManager>,
+		retry: RetryConfig,
+		max_request_bytes: usize,
 	) -> Arc<Self> {
 		Arc::new(Self {
-			allow_node_ids,
+			authorizer,
 			job_seq: Arc::new(AtomicU64::new(1)),
+			jobs,
+			retry,
+			max_request_bytes,
 			endpoint,
 			store,
 		})
@@ -111,70 +181,237 @@ impl ProtocolHandler for ControlHandler {
 		conn: Connection,
 	) -> impl core::future::Future<Output = Result<(), AcceptError>> + Send {
 		// Clone the pieces needed to build a 'static future.
-		let allow_node_ids = self.allow_node_ids.clone();
+		let authorizer = self.authorizer.clone();
 		let endpoint = self.endpoint.clone();
 		let store = self.store.clone();
 		let job_seq = self.job_seq.clone();
+		let jobs = self.jobs.clone();
+		let retry = self.retry;
+		let max_request_bytes = self.max_request_bytes;
 
 		async move {
-			// 1) AuthZ by NodeId
+			// 1) Identify the peer; policy is decided once we know what it's asking for.
 			let peer = conn.remote_node_id().map_err(AcceptError::from_err)?;
-			if !allow_node_ids.contains(&peer) {
-				error!(%peer, "peer not allowed");
-				return Err(AcceptError::from_err(ControlError::Unauthorized));
-			}
 
 			// 2) Bi-stream: read request with explicit size limit
 			let (mut send, mut recv) = conn.accept_bi().await.map_err(AcceptError::from_err)?;
-			let request_buf = recv.read_to_end(CONTROL_MAX_BYTES).await.map_err(|e| {
+			let request_buf = recv.read_to_end(max_request_bytes).await.map_err(|e| {
 				AcceptError::from_err(ControlError::Read {
 					source: Box::new(e),
 				})
 			})?;
 
-			let begin: BeginBackup = serde_json::from_slice(&request_buf).map_err(|e| {
+			let envelope: RequestEnvelope = serde_json::from_slice(&request_buf).map_err(|e| {
 				AcceptError::from_err(ControlError::Parse {
 					source: e,
 				})
 			})?;
-			debug!(device = %begin.device_tag, tickets = begin.tickets.len(), "begin");
+			if envelope.version != CONTROL_PROTOCOL_VERSION {
+				error!(%peer, got = envelope.version, "unsupported protocol version");
+				return Err(AcceptError::from_err(ControlError::UnsupportedVersion {
+					got: envelope.version,
+				}));
+			}
+
+			// 3) Dispatch on the decoded operation. `Begin` authorizes itself
+			// against the request it carries (see below); every other op carries
+			// no `BeginBackup` to check, but still needs the peer to hold some
+			// grant before it can enumerate jobs or probe blobs.
+			if !matches!(envelope.request, ControlRequest::Begin(_)) {
+				authorizer
+					.authorize_peer(peer, envelope.token.as_deref())
+					.await
+					.map_err(AcceptError::from_err)?;
+			}
+			let response = match envelope.request {
+				ControlRequest::Begin(begin) => {
+					debug!(device = %begin.device_tag, tickets = begin.tickets.len(), "begin");
 
-			// 3) Download using FsStore downloader API
-			let job_id = job_seq.fetch_add(1, Ordering::Relaxed);
+					// AuthZ: policy can now depend on what's being asked.
+					let grant =
+						authorizer.authorize(peer, &begin).await.map_err(AcceptError::from_err)?;
+					if !grant.permits(&begin) {
+						error!(%peer, device = %begin.device_tag, "request exceeds granted capability");
+						return Err(AcceptError::from_err(ControlError::Unauthorized));
+					}
 
-			let mut downloaded = 0usize;
-			let mut failed = 0usize;
+					let job_id = job_seq.fetch_add(1, Ordering::Relaxed);
+					let total = begin.tickets.len();
+					jobs.start(job_id, begin.device_tag.clone(), total);
 
-			let downloader = store.downloader(&endpoint);
-			for t in &begin.tickets {
-				match t.parse::<BlobTicket>() {
-					Ok(ticket) => {
-						let provider = Some(ticket.node_addr().node_id);
-						match downloader.download(ticket.hash(), provider).await {
-							Ok(()) => downloaded += 1,
-							Err(e) => {
-								failed += 1;
-								error!(%e, %job_id, "download failed");
+					// Run the download on its own task so the job survives this
+					// connection, and stream a frame to the caller as each ticket
+					// completes instead of going silent until the very end.
+					let (tx, mut rx) = tokio::sync::mpsc::channel::<ControlResponse>(16);
+					let task_jobs = jobs.clone();
+					let task_store = store.clone();
+					let task_endpoint = endpoint.clone();
+					let tickets = begin.tickets.clone();
+					let byte_quota = grant.byte_quota;
+					let download_task = tokio::spawn(async move {
+						let mut downloaded = 0usize;
+						let mut retried = 0usize;
+						let mut failed = 0usize;
+						let mut quota_skipped = 0usize;
+						let mut bytes_downloaded = 0u64;
+						let downloader = task_store.downloader(&task_endpoint);
+						for (i, t) in tickets.iter().enumerate() {
+							if byte_quota.is_some_and(|quota| bytes_downloaded >= quota) {
+								quota_skipped = tickets.len() - i;
+								warn!(
+									%job_id,
+									bytes_downloaded,
+									?byte_quota,
+									quota_skipped,
+									"byte quota reached, skipping remaining tickets"
+								);
+								break;
+							}
+							match t.parse::<BlobTicket>() {
+								Ok(ticket) => {
+									let hash = ticket.hash();
+									let provider = Some(ticket.node_addr().node_id);
+									let outcome = download_with_retry(&retry, || {
+										downloader.download(hash, provider)
+									})
+									.await;
+									if outcome.succeeded() {
+										downloaded += 1;
+										if outcome.was_retried() {
+											retried += 1;
+										}
+										bytes_downloaded += task_store.blob_size(&hash).await.unwrap_or(0);
+									} else {
+										failed += 1;
+										error!(%hash, ?outcome, %job_id, "download failed after retries");
+									}
+								}
+								Err(e) => {
+									failed += 1;
+									error!(ticket = %t, %e, "ticket parse failed");
+								}
 							}
+							task_jobs.record(job_id, downloaded, failed);
+							let _ = tx
+								.send(ControlResponse::Progress {
+									job_id,
+									downloaded,
+									failed,
+									total,
+								})
+								.await;
 						}
+						task_jobs.finish(job_id);
+						info!(job_id, downloaded, retried, failed, quota_skipped, "completed");
+						let _ = tx
+							.send(ControlResponse::Ack(CompletionAck {
+								job_id,
+								ok: failed == 0,
+								downloaded,
+								retried,
+								failed,
+								quota_skipped,
+								error: (failed > 0).then(|| format!("{failed} failures")),
+							}))
+							.await;
+					});
+					jobs.set_abort_handle(job_id, download_task.abort_handle());
+
+					// Forward frames as they arrive, one JSON object per line,
+					// until the task sends its terminal Ack and closes the channel
+					// (or is aborted by `Cancel`, which closes it with no Ack).
+					while let Some(frame) = rx.recv().await {
+						let out = ResponseEnvelope {
+							version: CONTROL_PROTOCOL_VERSION,
+							response: frame,
+						};
+						let mut bytes = serde_json::to_vec(&out).map_err(|e| {
+							let io = std::io::Error::other(e);
+							AcceptError::from_err(ControlError::Reply {
+								source: io,
+							})
+						})?;
+						bytes.push(b'\n');
+						send.write_all(&bytes).await.map_err(|e| {
+							AcceptError::from_err(ControlError::Reply {
+								source: e.into(),
+							})
+						})?;
+					}
+					send.finish().map_err(|e| {
+						AcceptError::from_err(ControlError::Reply {
+							source: e.into(),
+						})
+					})?;
+					return Ok(());
+				}
+				ControlRequest::Status {
+					job_id,
+				} => match jobs.get(job_id) {
+					Some(job) => ControlResponse::Status(JobStatus {
+						job_id,
+						device_tag: job.device_tag,
+						total: job.total,
+						downloaded: job.downloaded,
+						failed: job.failed,
+						done: job.status != JobStatusKind::Running,
+					}),
+					None => ControlResponse::Error {
+						message: format!("no such job {job_id}"),
+					},
+				},
+				ControlRequest::List => ControlResponse::Jobs(
+					jobs.list()
+						.into_iter()
+						.map(|(job_id, job)| JobSummary {
+							job_id,
+							device_tag: job.device_tag,
+							done: job.status != JobStatusKind::Running,
+						})
+						.collect(),
+				),
+				ControlRequest::Verify {
+					hashes,
+				} => {
+					let mut results = Vec::with_capacity(hashes.len());
+					for hash in hashes {
+						let present = match hash.parse::<iroh_blobs::Hash>() {
+							Ok(h) => store.has(&h).await.unwrap_or(false),
+							Err(e) => {
+								debug!(%hash, %e, "verify: bad hash");
+								false
+							}
+						};
+						results.push(VerifyResult {
+							hash,
+							present,
+						});
 					}
-					Err(e) => {
-						failed += 1;
-						error!(ticket = %t, %e, "ticket parse failed");
+					ControlResponse::Verified {
+						results,
 					}
 				}
-			}
+				ControlRequest::Cancel {
+					job_id,
+				} => match jobs.cancel(job_id) {
+					CancelOutcome::Cancelled => ControlResponse::Cancelled {
+						job_id,
+					},
+					CancelOutcome::NotFound => ControlResponse::Error {
+						message: format!("no such job {job_id}"),
+					},
+					CancelOutcome::AlreadyDone => ControlResponse::Error {
+						message: format!("job {job_id} already finished"),
+					},
+				},
+			};
 
 			// 4) Reply
-			let ack = CompletionAck {
-				job_id,
-				ok: failed == 0,
-				downloaded,
-				failed,
-				error: (failed > 0).then(|| format!("{failed} failures")),
+			let out = ResponseEnvelope {
+				version: CONTROL_PROTOCOL_VERSION,
+				response,
 			};
-
-			let bytes = serde_json::to_vec(&ack).map_err(|e| {
+			let bytes = serde_json::to_vec(&out).map_err(|e| {
 				let io = std::io::Error::other(e);
 				AcceptError::from_err(ControlError::Reply {
 					source: io,
@@ -193,7 +430,6 @@ impl ProtocolHandler for ControlHandler {
 				})
 			})?;
 
-			info!(job_id, downloaded, failed, "completed");
 			Ok(())
 		}
 	}
@@ -0,0 +1,81 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{BeginBackup, CompletionAck};
+
+/// Protocol version this build of homenect speaks. Bump whenever the wire
+/// schema below changes incompatibly.
+pub const CONTROL_PROTOCOL_VERSION: u16 = 1;
+
+/// Wraps every request with a version header so old/new peers fail cleanly
+/// rather than mis-parsing a JSON shape they don't understand.
+#[derive(Debug, Deserialize)]
+pub struct RequestEnvelope {
+	pub version: u16,
+	pub request: ControlRequest,
+	/// Capability token for ops with no `BeginBackup` of their own to carry
+	/// one (`Status`, `Cancel`, `List`, `Verify`); ignored for `Begin`, which
+	/// carries its own. See `Authorizer::authorize_peer`.
+	#[serde(default)]
+	pub token: Option<String>,
+}
+
+/// One control-plane operation. `Begin` is the original round trip; the
+/// rest let a client poll, cancel and list jobs and verify stored blobs.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum ControlRequest {
+	Begin(BeginBackup),
+	Status { job_id: u64 },
+	Cancel { job_id: u64 },
+	List,
+	Verify { hashes: Vec<String> },
+}
+
+/// Wraps every response with the same version header carried on the request.
+#[derive(Debug, Serialize)]
+pub struct ResponseEnvelope {
+	pub version: u16,
+	pub response: ControlResponse,
+}
+
+/// One control-plane response, tagged by kind.
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ControlResponse {
+	/// One ticket's worth of progress on an in-flight `Begin` job. Zero or
+	/// more of these precede the terminal `Ack` for the same `job_id`; a job
+	/// stopped by `Cancel` sends no `Ack`, its stream just ends.
+	Progress { job_id: u64, downloaded: usize, failed: usize, total: usize },
+	Ack(CompletionAck),
+	Status(JobStatus),
+	Cancelled { job_id: u64 },
+	Jobs(Vec<JobSummary>),
+	Verified { results: Vec<VerifyResult> },
+	Error { message: String },
+}
+
+/// Snapshot of one job's progress, returned by `Status`.
+#[derive(Debug, Serialize)]
+pub struct JobStatus {
+	pub job_id: u64,
+	pub device_tag: String,
+	pub total: usize,
+	pub downloaded: usize,
+	pub failed: usize,
+	pub done: bool,
+}
+
+/// One row of a `List` response.
+#[derive(Debug, Serialize)]
+pub struct JobSummary {
+	pub job_id: u64,
+	pub device_tag: String,
+	pub done: bool,
+}
+
+/// Whether the store already holds a given hash, returned by `Verify`.
+#[derive(Debug, Serialize)]
+pub struct VerifyResult {
+	pub hash: String,
+	pub present: bool,
+}
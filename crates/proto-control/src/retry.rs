@@ -0,0 +1,113 @@
+use std::{future::Future, time::Duration};
+
+use tracing::warn;
+
+/// Per-ticket download timeout and retry bounds.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryConfig {
+	pub download_timeout: Duration,
+	pub max_attempts: u32,
+	pub backoff_base: Duration,
+	pub backoff_cap: Duration,
+}
+
+impl Default for RetryConfig {
+	fn default() -> Self {
+		Self {
+			download_timeout: Duration::from_secs(30),
+			max_attempts: 5,
+			backoff_base: Duration::from_millis(500),
+			backoff_cap: Duration::from_secs(30),
+		}
+	}
+}
+
+/// How a single ticket's download ultimately went.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TicketOutcome {
+	Succeeded,
+	RetriedSucceeded { attempts: u32 },
+	FailedAfterRetries { attempts: u32 },
+}
+
+impl TicketOutcome {
+	pub fn succeeded(self) -> bool {
+		!matches!(self, TicketOutcome::FailedAfterRetries { .. })
+	}
+
+	pub fn was_retried(self) -> bool {
+		matches!(self, TicketOutcome::RetriedSucceeded { .. })
+	}
+}
+
+/// Run `download` under a timeout, retrying on timeout or transient error with
+/// jittered exponential backoff until it succeeds or `cfg.max_attempts` is used up.
+///
+/// A permanent error (the provider telling us it doesn't have the blob,
+/// recognized by [`is_permanent_error`]'s necessarily best-effort string
+/// match) fails immediately instead of burning the remaining attempts and
+/// backoff on a retry that can't succeed. `download` is called once per
+/// attempt; a ticket-parse error should never reach here, since it isn't a
+/// download failure at all.
+pub async fn download_with_retry<F, Fut, E>(cfg: &RetryConfig, mut download: F) -> TicketOutcome
+where
+	F: FnMut() -> Fut,
+	Fut: Future<Output = Result<(), E>>,
+	E: std::fmt::Display,
+{
+	let mut attempt = 0u32;
+	loop {
+		attempt += 1;
+		match tokio::time::timeout(cfg.download_timeout, download()).await {
+			Ok(Ok(())) if attempt == 1 => return TicketOutcome::Succeeded,
+			Ok(Ok(())) => {
+				return TicketOutcome::RetriedSucceeded {
+					attempts: attempt,
+				}
+			}
+			Ok(Err(e)) => {
+				if is_permanent_error(&e.to_string()) {
+					warn!(attempt, %e, "download failed permanently, not retrying");
+					return TicketOutcome::FailedAfterRetries {
+						attempts: attempt,
+					};
+				}
+				warn!(attempt, %e, "download failed");
+			}
+			Err(_) => warn!(attempt, timeout = ?cfg.download_timeout, "download timed out"),
+		}
+
+		if attempt >= cfg.max_attempts {
+			return TicketOutcome::FailedAfterRetries {
+				attempts: attempt,
+			};
+		}
+		tokio::time::sleep(backoff_with_jitter(cfg.backoff_base, cfg.backoff_cap, attempt)).await;
+	}
+}
+
+/// Best-effort classification of a download error as not worth retrying.
+///
+/// The closure's error type is opaque (`E: Display` only, to avoid naming
+/// iroh_blobs' concrete downloader error types here), so this necessarily
+/// works off the rendered message rather than a typed variant. That makes it
+/// a blunt instrument: we deliberately only match phrases that plausibly mean
+/// "the provider told us this blob doesn't exist," not generic words like
+/// "invalid" or "unsupported" that show up in transient connection/protocol
+/// errors too (e.g. "invalid response while connecting" is not permanent).
+/// A real not-found phrased some other way just gets retried to exhaustion
+/// like any other transient failure — worse than bailing out instantly, but
+/// safer than giving up early on a blob that was actually retrievable.
+fn is_permanent_error(message: &str) -> bool {
+	let message = message.to_ascii_lowercase();
+	["blob not found", "hash not found", "no such blob", "content not found"]
+		.iter()
+		.any(|needle| message.contains(needle))
+}
+
+fn backoff_with_jitter(base: Duration, cap: Duration, failed_attempts: u32) -> Duration {
+	let shift = failed_attempts.saturating_sub(1).min(16);
+	let exp = base.saturating_mul(1u32 << shift).min(cap);
+	let jitter_ms = rand::random::<u64>() % (exp.as_millis() as u64 / 4 + 1);
+	exp + Duration::from_millis(jitter_ms)
+}